@@ -1,7 +1,14 @@
 use log::*;
 
-use dns::{Request, Response};
+use dns::{Additional, Request, Response};
 use super::{Transport, Error, UdpTransport, TcpTransport};
+use super::system::SystemConfig;
+
+
+/// The default value advertised as the UDP payload size, used unless a
+/// different one is set with
+/// [`with_udp_payload_size`](AutoTransport::with_udp_payload_size).
+const DEFAULT_UDP_PAYLOAD_SIZE: u16 = 4096;
 
 
 /// The **automatic transport**, which sends DNS wire data using the UDP
@@ -10,6 +17,15 @@ use super::{Transport, Error, UdpTransport, TcpTransport};
 ///
 /// This is the default behaviour for many DNS clients.
 ///
+/// To cut down on the number of responses that need a second, slower TCP
+/// round-trip, every UDP request advertises support for larger UDP
+/// datagrams using an EDNS0 OPT record, so only responses that are too
+/// big even for that have to fall back to TCP at all. The exception is a
+/// transport made by [`system`](AutoTransport::system) on a host whose
+/// resolver configuration does not enable EDNS0: that one sends no OPT
+/// record unless a size is set with
+/// [`with_udp_payload_size`](AutoTransport::with_udp_payload_size).
+///
 /// # Examples
 ///
 /// ```no_run
@@ -34,6 +50,7 @@ use super::{Transport, Error, UdpTransport, TcpTransport};
 /// ```
 pub struct AutoTransport {
     addr: String,
+    udp_payload_size: Option<u16>,
 }
 
 impl AutoTransport {
@@ -41,13 +58,50 @@ impl AutoTransport {
     /// Creates a new automatic transport that connects to the given host.
     pub fn new(sa: impl Into<String>) -> Self {
         let addr = sa.into();
-        Self { addr }
+        Self { addr, udp_payload_size: Some(DEFAULT_UDP_PAYLOAD_SIZE) }
+    }
+
+    /// Sets the UDP payload size advertised to the server in the EDNS0 OPT
+    /// record sent with every request, allowing larger responses to come
+    /// back over UDP without needing a TCP retry.
+    pub fn with_udp_payload_size(mut self, udp_payload_size: u16) -> Self {
+        self.udp_payload_size = Some(udp_payload_size);
+        self
+    }
+
+    /// Creates a new automatic transport that queries the nameserver
+    /// configured on this system — `/etc/resolv.conf` on Unix, or the
+    /// network adapter settings on Windows — rather than one chosen by
+    /// the caller.
+    pub fn system() -> Result<Self, Error> {
+        let config = SystemConfig::load()?;
+
+        let addr = config.first_nameserver()
+            .ok_or_else(|| Error::NetworkError("no nameservers configured on this system".into()))?;
+
+        let mut transport = Self::new(addr);
+        if ! config.use_edns0 {
+            transport.udp_payload_size = None;
+        }
+
+        Ok(transport)
     }
 }
 
 
 impl Transport for AutoTransport {
     fn send(&self, request: &Request) -> Result<Response, Error> {
+        let mut request = request.clone();
+        if let Some(udp_payload_size) = self.udp_payload_size {
+            // Merge into whatever the caller already set (e.g. the DO bit
+            // for a DNSSEC query) rather than clobbering it with a
+            // payload-size-only OPT record.
+            request.additional = Some(match request.additional.take() {
+                Some(existing) => existing.with_udp_payload_size(udp_payload_size),
+                None => Additional::for_payload_size(udp_payload_size),
+            });
+        }
+
         let udp_transport = UdpTransport::new(&self.addr);
         let udp_response = udp_transport.send(&request)?;
 
@@ -55,7 +109,7 @@ impl Transport for AutoTransport {
             return Ok(udp_response);
         }
 
-        debug!("Truncated flag set, so switching to TCP");
+        debug!("Truncated flag set even with the advertised UDP payload size (if any), so switching to TCP");
 
         let tcp_transport = TcpTransport::new(&self.addr);
         let tcp_response = tcp_transport.send(&request)?;
@@ -0,0 +1,308 @@
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::*;
+
+use dns::{Request, Response};
+use super::{Transport, Error, AutoTransport};
+use super::system::SystemConfig;
+
+
+/// The default budget given to each racing server to answer before its
+/// result is given up on, used unless a different one is set with
+/// [`with_per_server_timeout`](MultiTransport::with_per_server_timeout).
+const DEFAULT_PER_SERVER_TIMEOUT: Duration = Duration::from_secs(5);
+
+
+/// How a [`MultiTransport`] should use the servers it holds.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MultiMode {
+
+    /// Try each server in order, moving on to the next one only if the
+    /// current one errors or times out.
+    Failover,
+
+    /// Send the request to the first two servers at once, and return
+    /// whichever one answers first, falling back to the rest of the list
+    /// in order if both of those fail.
+    Race,
+}
+
+
+/// The **multi-server transport**, which wraps several upstream addresses
+/// and gives up on a server only to move onto the next one, rather than
+/// letting a single dead or slow nameserver stall the whole lookup.
+///
+/// # Examples
+///
+/// ```no_run
+/// use dns_transport::{Transport, MultiTransport, MultiMode};
+/// use dns::{Request, Flags, Query, Labels, QClass, qtype, record::NS};
+///
+/// let query = Query {
+///     qname: Labels::encode("dns.lookup.dog").unwrap(),
+///     qclass: QClass::IN,
+///     qtype: qtype!(NS),
+/// };
+///
+/// let request = Request {
+///     transaction_id: 0xABCD,
+///     flags: Flags::query(),
+///     query: query,
+///     additional: None,
+/// };
+///
+/// let transport = MultiTransport::new(vec!["1.1.1.1".into(), "8.8.8.8".into()], MultiMode::Race);
+/// transport.send(&request);
+/// ```
+pub struct MultiTransport {
+    addrs: Vec<String>,
+    mode: MultiMode,
+    per_server_timeout: Duration,
+}
+
+impl MultiTransport {
+
+    /// Creates a new multi-server transport that sends requests to the
+    /// given addresses according to the given mode.
+    pub fn new(addrs: Vec<String>, mode: MultiMode) -> Self {
+        Self { addrs, mode, per_server_timeout: DEFAULT_PER_SERVER_TIMEOUT }
+    }
+
+    /// Creates a new multi-server transport targeting every nameserver
+    /// configured on this system — `/etc/resolv.conf` on Unix, or the
+    /// network adapter settings on Windows — instead of just the first
+    /// one, so the redundancy of having several configured isn't thrown
+    /// away.
+    pub fn system(mode: MultiMode) -> Result<Self, Error> {
+        let config = SystemConfig::load()?;
+
+        if config.nameservers.is_empty() {
+            return Err(Error::NetworkError("no nameservers configured on this system".into()));
+        }
+
+        Ok(Self::new(config.nameservers, mode))
+    }
+
+    /// Sets the budget given to each server raced against in
+    /// [`MultiMode::Race`] before its answer is given up on. This is the
+    /// per-server timeout the request asked for: a single dead racer
+    /// can delay the result by at most this long, rather than whichever
+    /// fixed amount of time it takes every server to reply or error out.
+    pub fn with_per_server_timeout(mut self, per_server_timeout: Duration) -> Self {
+        self.per_server_timeout = per_server_timeout;
+        self
+    }
+
+    fn send_failover(&self, request: &Request, addrs: &[String]) -> Result<Response, Vec<String>> {
+        let mut errors = Vec::new();
+
+        for addr in addrs {
+            debug!("Trying nameserver {:?}", addr);
+
+            match AutoTransport::new(addr.clone()).send(request) {
+                Ok(response) => return Ok(response),
+                Err(e) => errors.push(format!("{}: {}", addr, e)),
+            }
+        }
+
+        Err(errors)
+    }
+
+    /// Sends the request to `addrs` at once, returning whichever answers
+    /// first. The handles for any racers still outstanding when this
+    /// returns are handed to a background thread to be joined, rather
+    /// than abandoned, so their sockets are cleaned up once those racers
+    /// eventually do finish.
+    fn race(&self, request: &Request, addrs: &[String]) -> Result<Response, Vec<String>> {
+        let (tx, rx) = mpsc::channel();
+        let mut handles = Vec::with_capacity(addrs.len());
+
+        for addr in addrs {
+            let tx = tx.clone();
+            let addr = addr.clone();
+            let request = request.clone();
+
+            handles.push(thread::spawn(move || {
+                let result = AutoTransport::new(addr.clone()).send(&request)
+                    .map_err(|e| format!("{}: {}", addr, e));
+                let _ = tx.send((addr, result));
+            }));
+        }
+
+        drop(tx);
+
+        // Measure against a single deadline rather than giving each
+        // `recv_timeout` call its own fresh budget, or two hung racers
+        // would take `2 * per_server_timeout` instead of the one timeout
+        // `with_per_server_timeout` promises.
+        let deadline = Instant::now() + self.per_server_timeout;
+
+        let mut errors = Vec::new();
+        let mut winner = None;
+        let mut pending = addrs.to_vec();
+
+        for _ in 0 .. addrs.len() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            match rx.recv_timeout(remaining) {
+                Ok((_, Ok(response))) => { winner = Some(response); break; }
+                Ok((addr, Err(e))) => {
+                    if let Some(index) = pending.iter().position(|a| *a == addr) {
+                        pending.remove(index);
+                    }
+                    errors.push(e);
+                }
+                Err(_) => {
+                    // Name every racer that has not answered by the deadline.
+                    errors.extend(pending.drain(..).map(|addr| format!("{}: timed out after {:?}", addr, self.per_server_timeout)));
+                    break;
+                }
+            }
+        }
+
+        reap(handles);
+
+        match winner {
+            Some(response) => Ok(response),
+            None => Err(errors),
+        }
+    }
+
+    fn send_race(&self, request: &Request) -> Result<Response, Error> {
+        let (first_two, rest) = self.addrs.split_at(self.addrs.len().min(2));
+
+        if first_two.len() < 2 {
+            return self.send_failover(request, &self.addrs).map_err(|errors| all_failed(&errors));
+        }
+
+        let mut errors = match self.race(request, first_two) {
+            Ok(response) => return Ok(response),
+            Err(errors) => errors,
+        };
+
+        debug!("Both raced nameservers failed, falling back to the rest of the list");
+
+        match self.send_failover(request, rest) {
+            Ok(response) => Ok(response),
+            Err(rest_errors) => { errors.extend(rest_errors); Err(all_failed(&errors)) }
+        }
+    }
+}
+
+
+/// Joins the given thread handles on a detached background thread,
+/// instead of either blocking the caller on stragglers or abandoning the
+/// handles without ever looking at them again.
+fn reap(handles: Vec<thread::JoinHandle<()>>) {
+    thread::spawn(move || {
+        for handle in handles {
+            let _ = handle.join();
+        }
+    });
+}
+
+/// Builds the combined error returned once every nameserver in `errors`
+/// has failed.
+fn all_failed(errors: &[String]) -> Error {
+    Error::NetworkError(format!("every nameserver failed: {}", errors.join("; ")))
+}
+
+
+impl Transport for MultiTransport {
+    fn send(&self, request: &Request) -> Result<Response, Error> {
+        match self.mode {
+            MultiMode::Failover => self.send_failover(request, &self.addrs).map_err(|errors| all_failed(&errors)),
+            MultiMode::Race => self.send_race(request),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use dns::{Flags, Query, Labels, QClass, qtype, record::NS};
+
+    use super::*;
+
+    fn network_error_message(error: Error) -> String {
+        match error {
+            Error::NetworkError(message) => message,
+            _ => panic!("wrong error variant"),
+        }
+    }
+
+    fn sample_request() -> Request {
+        let query = Query {
+            qname: Labels::encode("dns.lookup.dog").unwrap(),
+            qclass: QClass::IN,
+            qtype: qtype!(NS),
+        };
+
+        Request {
+            transaction_id: 0xABCD,
+            flags: Flags::query(),
+            query,
+            additional: None,
+        }
+    }
+
+    #[test]
+    fn all_failed_joins_every_error() {
+        let errors = vec![String::from("1.1.1.1: timed out"), String::from("8.8.8.8: connection refused")];
+        let message = network_error_message(all_failed(&errors));
+        assert_eq!(message, "every nameserver failed: 1.1.1.1: timed out; 8.8.8.8: connection refused");
+    }
+
+    #[test]
+    fn all_failed_with_no_servers() {
+        let message = network_error_message(all_failed(&[]));
+        assert_eq!(message, "every nameserver failed: ");
+    }
+
+    // These addresses are in TEST-NET-2 (RFC 5737), reserved for
+    // documentation and guaranteed not to route anywhere, so every racer
+    // genuinely hangs until our own per-server timeout gives up on it —
+    // exactly the worst case `MultiMode::Race` has to handle.
+    const UNREACHABLE_ADDRS: [&str; 3] = ["198.51.100.1:53", "198.51.100.2:53", "198.51.100.3:53"];
+
+    #[test]
+    fn send_race_falls_back_without_double_wrapping_the_error() {
+        // Regression test for a bug where falling back from a failed race
+        // to the rest of the server list produced a doubled-up message
+        // like "every nameserver failed: ...; every nameserver failed: ...".
+        // This exercises the real `send_race` control flow, not a
+        // hand-built error vector, so reintroducing the bug fails it.
+        let transport = MultiTransport::new(
+            UNREACHABLE_ADDRS.iter().map(|&s| s.to_owned()).collect(),
+            MultiMode::Race,
+        ).with_per_server_timeout(Duration::from_millis(50));
+
+        let error = transport.send(&sample_request()).unwrap_err();
+        let message = network_error_message(error);
+
+        assert_eq!(message.matches("every nameserver failed").count(), 1);
+        assert!(message.contains("198.51.100.1:53"));
+        assert!(message.contains("198.51.100.3:53"));
+    }
+
+    #[test]
+    fn send_race_waits_for_one_deadline_not_one_per_racer() {
+        // Regression test: recv_timeout used to be called fresh on every
+        // loop iteration, so two hung racers took 2 * per_server_timeout
+        // instead of the single timeout `with_per_server_timeout` promises.
+        let per_server_timeout = Duration::from_millis(50);
+        let transport = MultiTransport::new(
+            UNREACHABLE_ADDRS[..2].iter().map(|&s| s.to_owned()).collect(),
+            MultiMode::Race,
+        ).with_per_server_timeout(per_server_timeout);
+
+        let started = Instant::now();
+        let _ = transport.send(&sample_request());
+        let elapsed = started.elapsed();
+
+        assert!(elapsed < per_server_timeout * 4,
+            "send_race took {:?}, expected well under {:?} for a single deadline", elapsed, per_server_timeout * 4);
+    }
+}
@@ -0,0 +1,203 @@
+//! Reading the nameservers configured on the host operating system, for
+//! use by [`AutoTransport::system`](super::AutoTransport::system).
+
+use super::Error;
+
+
+/// The parts of the host’s resolver configuration that are relevant to a
+/// transport: the nameservers to query, the search list, and whether
+/// EDNS0 is enabled.
+#[derive(Debug, PartialEq)]
+pub struct SystemConfig {
+
+    /// The nameserver addresses, in the order they were configured.
+    pub nameservers: Vec<String>,
+
+    /// The search-list domains, taken from the last `search` or `domain`
+    /// line (whichever appeared last, matching the usual resolver
+    /// behaviour where the two are mutually exclusive). Empty if neither
+    /// was present.
+    ///
+    /// No transport in this crate expands unqualified names against a
+    /// search list yet — that happens before a request is built, not
+    /// while it's being sent — so this is parsed for a future query-
+    /// building layer to consume, not used here.
+    pub search: Vec<String>,
+
+    /// Whether the `edns0` option was present, enabling EDNS0 support.
+    pub use_edns0: bool,
+
+    /// The value of the `attempts` option, if one was given. Parsed for
+    /// completeness, but not yet consumed by any transport — none of them
+    /// retry a single server yet, so there's nowhere for this to plug in.
+    pub attempts: Option<u32>,
+}
+
+impl SystemConfig {
+
+    /// Reads the nameservers configured for this machine, platform by
+    /// platform.
+    pub fn load() -> Result<Self, Error> {
+        load_platform_config()
+    }
+
+    /// The first configured nameserver, if there is one.
+    pub fn first_nameserver(&self) -> Option<&str> {
+        self.nameservers.first().map(String::as_str)
+    }
+}
+
+
+#[cfg(unix)]
+fn load_platform_config() -> Result<SystemConfig, Error> {
+    let contents = std::fs::read_to_string("/etc/resolv.conf")
+        .map_err(|e| Error::NetworkError(format!("could not read /etc/resolv.conf: {}", e)))?;
+
+    Ok(parse_resolv_conf(&contents))
+}
+
+/// Parses the contents of a `resolv.conf` file, picking out the
+/// `nameserver` lines for server addresses, the search list from `search`
+/// or `domain` lines, and the `edns0` and `attempts:N` entries from any
+/// `options` line.
+#[cfg(unix)]
+fn parse_resolv_conf(contents: &str) -> SystemConfig {
+    let mut nameservers = Vec::new();
+    let mut search = Vec::new();
+    let mut use_edns0 = false;
+    let mut attempts = None;
+
+    for line in contents.lines() {
+        let mut words = line.split_whitespace();
+
+        // Matching on the first whitespace-separated word, rather than a
+        // string prefix, so a line like `searchlight 1.2.3.4` isn't
+        // mistaken for a `search` directive.
+        let keyword = match words.next() {
+            Some(keyword) if ! keyword.starts_with(';') && ! keyword.starts_with('#') => keyword,
+            _ => continue,
+        };
+
+        match keyword {
+            "nameserver" => {
+                if let Some(addr) = words.next() {
+                    nameservers.push(addr.into());
+                }
+            }
+            "search" => {
+                search = words.map(String::from).collect();
+            }
+            "domain" => {
+                if let Some(domain) = words.next() {
+                    search = vec![domain.into()];
+                }
+            }
+            "options" => {
+                for opt in words {
+                    if opt == "edns0" {
+                        use_edns0 = true;
+                    }
+                    else if let Some(n) = opt.strip_prefix("attempts:") {
+                        attempts = n.parse().ok();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    SystemConfig { nameservers, search, use_edns0, attempts }
+}
+
+#[cfg(windows)]
+fn load_platform_config() -> Result<SystemConfig, Error> {
+    let adapters = ipconfig::get_adapters()
+        .map_err(|e| Error::NetworkError(format!("could not read adapter settings: {}", e)))?;
+
+    let nameservers = adapters.iter()
+        .flat_map(|adapter| adapter.dns_servers())
+        .map(std::net::IpAddr::to_string)
+        .collect::<Vec<_>>();
+
+    // Windows reads its search list and suffixes from the same adapter
+    // settings, but `ipconfig::Adapter` doesn't expose them, so this is
+    // left empty rather than guessed at.
+    let search = Vec::new();
+
+    // Windows does not have a resolv.conf-style `options edns0` or
+    // `attempts:N`; its stub resolver has supported EDNS0 since Windows 8,
+    // so treat it as always on, and leave the retry count unset.
+    Ok(SystemConfig { nameservers, search, use_edns0: true, attempts: None })
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finds_nameserver() {
+        let input = "nameserver 127.0.0.53\noptions edns0 trust-ad\n";
+        let config = parse_resolv_conf(input);
+        assert_eq!(config.nameservers, vec![String::from("127.0.0.53")]);
+        assert!(config.use_edns0);
+    }
+
+    #[test]
+    fn ignores_comments_and_finds_search() {
+        let input = "# a comment\nsearch example.com corp.example.com\nnameserver 1.1.1.1\n";
+        let config = parse_resolv_conf(input);
+        assert_eq!(config.nameservers, vec![String::from("1.1.1.1")]);
+        assert_eq!(config.search, vec![String::from("example.com"), String::from("corp.example.com")]);
+        assert!(! config.use_edns0);
+    }
+
+    #[test]
+    fn domain_sets_a_single_entry_search_list() {
+        let input = "nameserver 1.1.1.1\ndomain example.com\n";
+        let config = parse_resolv_conf(input);
+        assert_eq!(config.search, vec![String::from("example.com")]);
+    }
+
+    #[test]
+    fn a_later_search_line_overrides_an_earlier_domain_line() {
+        let input = "domain example.com\nsearch corp.example.com\n";
+        let config = parse_resolv_conf(input);
+        assert_eq!(config.search, vec![String::from("corp.example.com")]);
+    }
+
+    #[test]
+    fn does_not_mistake_a_longer_word_for_the_search_keyword() {
+        let input = "searchlight 1.2.3.4\nnameserver 1.1.1.1\n";
+        let config = parse_resolv_conf(input);
+        assert!(config.search.is_empty());
+        assert_eq!(config.nameservers, vec![String::from("1.1.1.1")]);
+    }
+
+    #[test]
+    fn empty_file_has_no_nameservers() {
+        let config = parse_resolv_conf("");
+        assert_eq!(config.first_nameserver(), None);
+    }
+
+    #[test]
+    fn finds_multiple_nameservers_in_order() {
+        let input = "nameserver 1.1.1.1\nnameserver 8.8.8.8\n";
+        let config = parse_resolv_conf(input);
+        assert_eq!(config.nameservers, vec![String::from("1.1.1.1"), String::from("8.8.8.8")]);
+    }
+
+    #[test]
+    fn finds_attempts_option() {
+        let input = "nameserver 1.1.1.1\noptions attempts:3 edns0\n";
+        let config = parse_resolv_conf(input);
+        assert_eq!(config.attempts, Some(3));
+        assert!(config.use_edns0);
+    }
+
+    #[test]
+    fn missing_attempts_option_is_none() {
+        let config = parse_resolv_conf("nameserver 1.1.1.1\n");
+        assert_eq!(config.attempts, None);
+    }
+}